@@ -1,14 +1,112 @@
 use anyhow::{anyhow, Result};
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use bytes::BufMut;
-use std::io::Cursor;
-use std::net::UdpSocket;
+use idna::{domain_to_ascii, domain_to_unicode};
+use resolver::Resolver;
+use std::io::{Cursor, Read, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, TcpListener, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use zone::Zone;
+
+mod resolver;
+mod zone;
+
+/// Upstream recursive resolver we forward unanswered questions to.
+const DEFAULT_UPSTREAM: &str = "1.1.1.1:53";
+
+/// UDP payload size assumed for clients that don't advertise EDNS0 support,
+/// and the buffer size the resolver receives upstream replies into (its
+/// forwarded queries carry no OPT record).
+const STANDARD_UDP_SIZE: usize = 512;
+
+/// UDP payload size we advertise in our own EDNS0 OPT records.
+const OUR_UDP_PAYLOAD_SIZE: u16 = 4096;
+
+/// Maximum number of compression pointers to follow while decoding a single
+/// name. Real zones never nest this deep; anything beyond it is almost
+/// certainly a pointer loop.
+const MAX_COMPRESSION_JUMPS: usize = 5;
+
+/// Decodes an RFC 1035 domain name starting at the cursor's current
+/// position, following message-compression pointers (section 4.1.4).
+///
+/// On return the cursor is left just past the first pointer encountered (or
+/// past the terminating zero octet if the name was never compressed), which
+/// is the correct resume point for the rest of the message. A bounded number
+/// of jumps are followed so a packet can't wedge the server in a pointer
+/// cycle. The wire form is ASCII-compatible encoding (`xn--...`); it's
+/// converted back to Unicode (IDNA) before being returned.
+fn read_name(buf: &mut Cursor<&[u8]>) -> Result<String> {
+    let mut labels = Vec::new();
+    let mut pos = buf.position();
+    let mut jumps = 0usize;
+    let mut resume_pos = None;
+
+    loop {
+        buf.set_position(pos);
+        let len = buf.read_u8()?;
+
+        if len == 0 {
+            pos = buf.position();
+            break;
+        } else if len & 0xC0 == 0xC0 {
+            let lo = buf.read_u8()?;
+            let offset = (((len & 0x3F) as u64) << 8) | lo as u64;
+
+            if resume_pos.is_none() {
+                resume_pos = Some(buf.position());
+            }
+
+            jumps += 1;
+            if jumps > MAX_COMPRESSION_JUMPS {
+                return Err(anyhow!("Too many compression pointers"));
+            }
+            pos = offset;
+        } else {
+            let mut label = vec![0u8; len as usize];
+            buf.read_exact(&mut label)?;
+            labels.push(String::from_utf8_lossy(&label).into_owned());
+            pos = buf.position();
+        }
+    }
+
+    buf.set_position(resume_pos.unwrap_or(pos));
+
+    let (name, result) = domain_to_unicode(&labels.join("."));
+    result.map_err(|e| anyhow!("Invalid domain name: {e:?}"))?;
+    Ok(name)
+}
+
+/// Encodes a domain name as a sequence of length-prefixed labels terminated
+/// by a zero octet. Non-ASCII labels are converted to their ACE/punycode
+/// (`xn--...`) form (IDNA) before the 63-byte per-label limit is enforced.
+/// Does not attempt message compression.
+fn encode_name(name: &str) -> Result<Vec<u8>> {
+    let ascii = domain_to_ascii(name).map_err(|e| anyhow!("Invalid domain name: {e:?}"))?;
+    let mut bytes = Vec::new();
+
+    for label in ascii.split('.') {
+        if label.is_empty() {
+            continue;
+        }
+        if label.len() > 63 {
+            return Err(anyhow!("Label is too big"));
+        }
+        bytes.extend((label.len() as u8).to_be_bytes());
+        bytes.extend(label.as_bytes());
+    }
+    bytes.put_u8(0);
+
+    Ok(bytes)
+}
 
 #[derive(Default, Debug, Clone)]
 struct DnsMessage {
     header: DnsHeader,
     questions: Vec<DnsQuestion>,
     answers: Vec<DnsAnswer>,
+    edns: Option<EdnsOpt>,
 }
 impl DnsMessage {
     fn new(header: DnsHeader, questions: Vec<DnsQuestion>, answers: Vec<DnsAnswer>) -> Self {
@@ -16,24 +114,58 @@ impl DnsMessage {
             header,
             questions,
             answers,
+            edns: None,
         }
     }
-    fn to_be_bytes(&self) -> Vec<u8> {
+    /// Encodes the message, truncating it to fit `max_size` (the UDP payload
+    /// size negotiated with the client) by dropping the answer section and
+    /// setting the `tc` bit, per RFC 1035 section 4.1.1.
+    fn to_be_bytes(&self, max_size: usize) -> Vec<u8> {
+        let full = self.encode();
+        if full.len() <= max_size {
+            return full;
+        }
+
+        let mut truncated = self.clone();
+        truncated.answers.clear();
+        truncated.header.ancount = 0;
+        truncated.header.tc = true;
+        truncated.encode()
+    }
+    fn encode(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(38);
 
         bytes.extend(self.header.to_be_bytes());
         bytes.extend(self.questions.iter().flat_map(|q| q.to_be_bytes().unwrap()));
         bytes.extend(self.answers.iter().flat_map(|a| a.to_be_bytes().unwrap()));
+        if let Some(opt) = self.edns {
+            bytes.extend(opt.to_be_bytes());
+        }
 
         bytes
     }
     fn read(&mut self, buf: &mut Cursor<&[u8]>) -> Result<()> {
         self.header.read(buf)?;
-        self.questions.iter_mut().try_for_each(|q| q.read(buf))?;
-        self.answers
-            .iter_mut()
-            .enumerate()
-            .try_for_each(|(i, a)| {a.name = self.questions[i].name.clone(); a.read(buf)})?;
+
+        self.questions = (0..self.header.qdcount)
+            .map(|_| {
+                let mut question = DnsQuestion::default();
+                question.read(buf)?;
+                Ok(question)
+            })
+            .collect::<Result<_>>()?;
+
+        self.answers = (0..self.header.ancount)
+            .map(|_| {
+                let mut answer = DnsAnswer::default();
+                answer.read(buf)?;
+                Ok(answer)
+            })
+            .collect::<Result<_>>()?;
+
+        skip_authority(buf, self.header.nscount)?;
+        self.edns = read_additional(buf, self.header.arcount)?;
+
         Ok(())
     }
 }
@@ -41,12 +173,29 @@ impl DnsMessage {
 #[derive(Default, Debug, Clone, Copy)]
 enum ResponseCode {
     #[default]
-    NoError = 0,
-    FormatError = 1,
-    ServerFailure = 2,
-    NameError = 3,
-    NotImplemented = 4,
-    Refused = 5,
+    NoError,
+    FormatError,
+    ServerFailure,
+    NameError,
+    NotImplemented,
+    Refused,
+    /// Any rcode we don't have a dedicated variant for (6-15 are reserved,
+    /// but upstream servers aren't obligated to stick to that), keyed by its
+    /// wire value.
+    Unknown(u8),
+}
+impl ResponseCode {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::NoError => 0,
+            Self::FormatError => 1,
+            Self::ServerFailure => 2,
+            Self::NameError => 3,
+            Self::NotImplemented => 4,
+            Self::Refused => 5,
+            Self::Unknown(value) => value,
+        }
+    }
 }
 impl From<u8> for ResponseCode {
     fn from(value: u8) -> Self {
@@ -57,7 +206,7 @@ impl From<u8> for ResponseCode {
             3 => Self::NameError,
             4 => Self::NotImplemented,
             5 => Self::Refused,
-            _ => panic!("Invalid value"),
+            other => Self::Unknown(other),
         }
     }
 }
@@ -123,7 +272,7 @@ impl DnsHeader {
                 | (self.rd as u8),
         );
         bytes.put_u8(
-            ((self.ra as u8) << 7u8) | ((self.z as u8) << 4u8) | ((self.rcode as u8) << 7u8),
+            ((self.ra as u8) << 7u8) | ((self.z as u8) << 4u8) | self.rcode.to_u8(),
         );
         bytes.put_u16(self.qdcount);
         bytes.put_u16(self.ancount);
@@ -135,47 +284,76 @@ impl DnsHeader {
     fn read(&mut self, buf: &mut Cursor<&[u8]>) -> Result<()> {
         self.id = buf.read_u16::<BigEndian>()?;
 
-        let flag = buf.read_u8()?;
-        self.qr = true;
-        self.opcode = (flag & 0b0111_0000) >> 3;
-        self.aa = false;
-        self.tc = false;
-        self.rd = (flag & 0b1) > 0;
-        self.ra = false;
-        self.z = false;
-        self.rcode = if self.opcode == 0 {
-            ResponseCode::NoError
-        } else {
-            ResponseCode::NotImplemented
-        };
+        let flag_hi = buf.read_u8()?;
+        self.qr = (flag_hi & 0b1000_0000) > 0;
+        self.opcode = (flag_hi & 0b0111_1000) >> 3;
+        self.aa = (flag_hi & 0b0000_0100) > 0;
+        self.tc = (flag_hi & 0b0000_0010) > 0;
+        self.rd = (flag_hi & 0b0000_0001) > 0;
 
-        self.qdcount = 1;
-        self.ancount = 1;
-        self.nscount = 0;
-        self.arcount = 0;
+        let flag_lo = buf.read_u8()?;
+        self.ra = (flag_lo & 0b1000_0000) > 0;
+        self.z = (flag_lo & 0b0100_0000) > 0;
+        self.rcode = ResponseCode::from(flag_lo & 0b0000_1111);
+
+        self.qdcount = buf.read_u16::<BigEndian>()?;
+        self.ancount = buf.read_u16::<BigEndian>()?;
+        self.nscount = buf.read_u16::<BigEndian>()?;
+        self.arcount = buf.read_u16::<BigEndian>()?;
         Ok(())
     }
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum RecordType {
     #[default]
-    A = 1,
-    NS = 2,
-    MD = 3,
-    MF = 4,
-    CNAME = 5,
-    SOA = 6,
-    MB = 7,
-    MG = 8,
-    MR = 9,
-    NULL = 10,
-    WKS = 11,
-    PTR = 12,
-    HINFO = 13,
-    MINFO = 14,
-    MX = 15,
-    TXT = 16,
+    A,
+    NS,
+    MD,
+    MF,
+    CNAME,
+    SOA,
+    MB,
+    MG,
+    MR,
+    NULL,
+    WKS,
+    PTR,
+    HINFO,
+    MINFO,
+    MX,
+    TXT,
+    AAAA,
+    OPT,
+    /// Any type we don't have a dedicated variant for, keyed by its wire
+    /// value. Keeps questions/answers for types like HTTPS or CAA round-trip
+    /// cleanly (see [`RData::Raw`]) instead of erroring out.
+    Unknown(u16),
+}
+impl RecordType {
+    fn to_u16(self) -> u16 {
+        match self {
+            Self::A => 1,
+            Self::NS => 2,
+            Self::MD => 3,
+            Self::MF => 4,
+            Self::CNAME => 5,
+            Self::SOA => 6,
+            Self::MB => 7,
+            Self::MG => 8,
+            Self::MR => 9,
+            Self::NULL => 10,
+            Self::WKS => 11,
+            Self::PTR => 12,
+            Self::HINFO => 13,
+            Self::MINFO => 14,
+            Self::MX => 15,
+            Self::TXT => 16,
+            Self::AAAA => 28,
+            Self::OPT => 41,
+            Self::Unknown(value) => value,
+        }
+    }
 }
 impl From<u16> for RecordType {
     fn from(value: u16) -> Self {
@@ -196,17 +374,101 @@ impl From<u16> for RecordType {
             14 => Self::MINFO,
             15 => Self::MX,
             16 => Self::TXT,
-            _ => panic!("Invalid value"),
+            28 => Self::AAAA,
+            41 => Self::OPT,
+            other => Self::Unknown(other),
         }
     }
 }
-#[derive(Default, Debug, Clone, Copy)]
+
+/// EDNS0 (RFC 6891) parameters, carried as an OPT pseudo-record in the
+/// additional section instead of a normal question/answer.
+#[derive(Debug, Clone, Copy)]
+struct EdnsOpt {
+    udp_payload_size: u16,
+    extended_rcode: u8,
+    version: u8,
+    flags: u16,
+}
+impl EdnsOpt {
+    fn to_be_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(11);
+        bytes.put_u8(0); // root name
+        bytes.extend(RecordType::OPT.to_u16().to_be_bytes());
+        bytes.extend(self.udp_payload_size.to_be_bytes());
+        bytes.put_u8(self.extended_rcode);
+        bytes.put_u8(self.version);
+        bytes.extend(self.flags.to_be_bytes());
+        bytes.extend(0u16.to_be_bytes()); // rdlength: no options
+        bytes
+    }
+}
+
+/// Skips over the authority section: we don't act on authority records, but
+/// we still have to step past them (name, type, class, ttl, rdlength, then
+/// rdata) to land on the right offset for the additional section.
+fn skip_authority(buf: &mut Cursor<&[u8]>, count: u16) -> Result<()> {
+    for _ in 0..count {
+        let _name = read_name(buf)?;
+        let _type = buf.read_u16::<BigEndian>()?;
+        let _class = buf.read_u16::<BigEndian>()?;
+        let _ttl = buf.read_u32::<BigEndian>()?;
+        let rdlength = buf.read_u16::<BigEndian>()?;
+        buf.set_position(buf.position() + rdlength as u64);
+    }
+
+    Ok(())
+}
+
+/// Reads the additional section, looking for an EDNS0 OPT pseudo-record.
+/// Other additional records aren't meaningful to us yet, so their RDATA is
+/// skipped rather than decoded.
+fn read_additional(buf: &mut Cursor<&[u8]>, count: u16) -> Result<Option<EdnsOpt>> {
+    let mut edns = None;
+
+    for _ in 0..count {
+        let _name = read_name(buf)?;
+        let r#type = buf.read_u16::<BigEndian>()?;
+        let class_or_udp_size = buf.read_u16::<BigEndian>()?;
+        let ttl = buf.read_u32::<BigEndian>()?;
+        let rdlength = buf.read_u16::<BigEndian>()?;
+        let rdata_pos = buf.position();
+
+        if r#type == RecordType::OPT.to_u16() {
+            edns = Some(EdnsOpt {
+                udp_payload_size: class_or_udp_size,
+                extended_rcode: ((ttl >> 24) & 0xFF) as u8,
+                version: ((ttl >> 16) & 0xFF) as u8,
+                flags: (ttl & 0xFFFF) as u16,
+            });
+        }
+
+        buf.set_position(rdata_pos + rdlength as u64);
+    }
+
+    Ok(edns)
+}
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum RecordClass {
     #[default]
-    IN = 1,
-    CS = 2,
-    CH = 3,
-    HS = 4,
+    IN,
+    CS,
+    CH,
+    HS,
+    /// Any class we don't have a dedicated variant for, keyed by its wire
+    /// value.
+    Unknown(u16),
+}
+impl RecordClass {
+    fn to_u16(self) -> u16 {
+        match self {
+            Self::IN => 1,
+            Self::CS => 2,
+            Self::CH => 3,
+            Self::HS => 4,
+            Self::Unknown(value) => value,
+        }
+    }
 }
 impl From<u16> for RecordClass {
     fn from(value: u16) -> Self {
@@ -215,7 +477,7 @@ impl From<u16> for RecordClass {
             2 => Self::CS,
             3 => Self::CH,
             4 => Self::HS,
-            _ => panic!("Invalid value"),
+            other => Self::Unknown(other),
         }
     }
 }
@@ -236,39 +498,16 @@ impl DnsQuestion {
     }
     fn to_be_bytes(&self) -> Result<Vec<u8>> {
         let mut bytes = Vec::with_capacity(10);
-        let (l1, l2) = self
-            .name
-            .rsplit_once('.')
-            .ok_or_else(|| anyhow!("Invalid label"))?;
-        if l1.len() > 255 || l2.len() > 255 {
-            return Err(anyhow!("Label is too big"));
-        }
-
-        bytes.extend((l1.len() as u8).to_be_bytes());
-        bytes.extend(l1.as_bytes());
-        bytes.extend((l2.len() as u8).to_be_bytes());
-        bytes.extend(l2.as_bytes());
-        bytes.put_u8(0);
-        bytes.extend((self.r#type as u16).to_be_bytes());
-        bytes.extend((self.class as u16).to_be_bytes());
+        bytes.extend(encode_name(&self.name)?);
+        bytes.extend(self.r#type.to_u16().to_be_bytes());
+        bytes.extend(self.class.to_u16().to_be_bytes());
 
         Ok(bytes)
     }
     fn read(&mut self, buf: &mut Cursor<&[u8]>) -> Result<()> {
-        let mut name = Vec::new();
-        loop {
-            let len = buf.read_u8().unwrap_or(0);
-            if len == 0 {
-                break;
-            }
-            for _ in 0..len {
-                name.push(buf.read_u8().unwrap_or(0));
-            }
-            buf.set_position(buf.position() + len as u64);
-            name.push(0);
-        }
-        self.r#type = RecordType::A;
-        self.class = RecordClass::IN;
+        self.name = read_name(buf)?;
+        self.r#type = RecordType::from(buf.read_u16::<BigEndian>()?);
+        self.class = RecordClass::from(buf.read_u16::<BigEndian>()?);
 
         Ok(())
     }
@@ -280,80 +519,245 @@ struct DnsAnswer {
     r#type: RecordType,
     class: RecordClass,
     ttl: u32,
-    length: u16,
-    data: Vec<u8>,
+    rdata: RData,
 }
 impl DnsAnswer {
-    fn new(
-        name: String,
-        r#type: RecordType,
-        class: RecordClass,
-        ttl: u32,
-        length: u16,
-        data: Vec<u8>,
-    ) -> Self {
+    fn new(name: String, r#type: RecordType, class: RecordClass, ttl: u32, rdata: RData) -> Self {
         Self {
             name,
             r#type,
             class,
             ttl,
-            length,
-            data,
+            rdata,
         }
     }
     fn to_be_bytes(&self) -> Result<Vec<u8>> {
         let mut bytes = Vec::with_capacity(16);
-        let (l1, l2) = self
-            .name
-            .rsplit_once('.')
-            .ok_or_else(|| anyhow!("Invalid label"))?;
-        if l1.len() > 255 || l2.len() > 255 {
-            panic!();
-        }
-
-        bytes.extend((l1.len() as u8).to_be_bytes());
-        bytes.extend(l1.as_bytes());
-        bytes.extend((l2.len() as u8).to_be_bytes());
-        bytes.extend(l2.as_bytes());
-        bytes.put_u8(0);
-        bytes.extend((self.r#type as u16).to_be_bytes());
-        bytes.extend((self.class as u16).to_be_bytes());
+        bytes.extend(encode_name(&self.name)?);
+        bytes.extend(self.r#type.to_u16().to_be_bytes());
+        bytes.extend(self.class.to_u16().to_be_bytes());
         bytes.extend(self.ttl.to_be_bytes());
-        bytes.extend(self.length.to_be_bytes());
-        bytes.extend(self.data.as_slice());
+
+        let rdata = self.rdata.to_be_bytes()?;
+        bytes.extend((rdata.len() as u16).to_be_bytes());
+        bytes.extend(rdata);
 
         Ok(bytes)
     }
     fn read(&mut self, buf: &mut Cursor<&[u8]>) -> Result<()> {
-        
-        self.r#type = RecordType::A;
-        self.class = RecordClass::IN;
-        self.ttl = 60;
-        self.length = 4;
-        self.data = vec![8,8,8,8];
+        self.name = read_name(buf)?;
+        self.r#type = RecordType::from(buf.read_u16::<BigEndian>()?);
+        self.class = RecordClass::from(buf.read_u16::<BigEndian>()?);
+        self.ttl = buf.read_u32::<BigEndian>()?;
+        let length = buf.read_u16::<BigEndian>()?;
+        self.rdata = RData::read(buf, self.r#type, length)?;
 
         Ok(())
     }
 }
 
-fn main() {
-    let udp_socket = UdpSocket::bind("127.0.0.1:2053").expect("Failed to bind to address");
-    let mut buf = [0u8; 512];
+/// The type-specific record data carried by a [`DnsAnswer`]. Unsupported
+/// types fall back to [`RData::Raw`] so the answer still round-trips even if
+/// we don't understand its contents.
+#[derive(Debug, Clone)]
+enum RData {
+    A(Ipv4Addr),
+    AAAA(Ipv6Addr),
+    CNAME(String),
+    NS(String),
+    PTR(String),
+    MX {
+        preference: u16,
+        exchange: String,
+    },
+    TXT(Vec<String>),
+    SOA {
+        mname: String,
+        rname: String,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    Raw(Vec<u8>),
+}
+impl Default for RData {
+    fn default() -> Self {
+        Self::Raw(Vec::new())
+    }
+}
+impl RData {
+    fn to_be_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self {
+            Self::A(addr) => bytes.extend(addr.octets()),
+            Self::AAAA(addr) => bytes.extend(addr.octets()),
+            Self::CNAME(name) | Self::NS(name) | Self::PTR(name) => {
+                bytes.extend(encode_name(name)?)
+            }
+            Self::MX {
+                preference,
+                exchange,
+            } => {
+                bytes.extend(preference.to_be_bytes());
+                bytes.extend(encode_name(exchange)?);
+            }
+            Self::TXT(strings) => {
+                for s in strings {
+                    if s.len() > 255 {
+                        return Err(anyhow!("TXT character-string is too big"));
+                    }
+                    bytes.push(s.len() as u8);
+                    bytes.extend(s.as_bytes());
+                }
+            }
+            Self::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                bytes.extend(encode_name(mname)?);
+                bytes.extend(encode_name(rname)?);
+                bytes.extend(serial.to_be_bytes());
+                bytes.extend(refresh.to_be_bytes());
+                bytes.extend(retry.to_be_bytes());
+                bytes.extend(expire.to_be_bytes());
+                bytes.extend(minimum.to_be_bytes());
+            }
+            Self::Raw(data) => bytes.extend(data),
+        }
+
+        Ok(bytes)
+    }
+    fn read(buf: &mut Cursor<&[u8]>, r#type: RecordType, length: u16) -> Result<Self> {
+        let end = buf.position() + length as u64;
+
+        let rdata = match r#type {
+            RecordType::A => {
+                let mut octets = [0u8; 4];
+                buf.read_exact(&mut octets)?;
+                Self::A(Ipv4Addr::from(octets))
+            }
+            RecordType::AAAA => {
+                let mut octets = [0u8; 16];
+                buf.read_exact(&mut octets)?;
+                Self::AAAA(Ipv6Addr::from(octets))
+            }
+            RecordType::CNAME => Self::CNAME(read_name(buf)?),
+            RecordType::NS => Self::NS(read_name(buf)?),
+            RecordType::PTR => Self::PTR(read_name(buf)?),
+            RecordType::MX => Self::MX {
+                preference: buf.read_u16::<BigEndian>()?,
+                exchange: read_name(buf)?,
+            },
+            RecordType::TXT => {
+                let mut strings = Vec::new();
+                while buf.position() < end {
+                    let len = buf.read_u8()?;
+                    let mut s = vec![0u8; len as usize];
+                    buf.read_exact(&mut s)?;
+                    strings.push(String::from_utf8_lossy(&s).into_owned());
+                }
+                Self::TXT(strings)
+            }
+            RecordType::SOA => Self::SOA {
+                mname: read_name(buf)?,
+                rname: read_name(buf)?,
+                serial: buf.read_u32::<BigEndian>()?,
+                refresh: buf.read_u32::<BigEndian>()?,
+                retry: buf.read_u32::<BigEndian>()?,
+                expire: buf.read_u32::<BigEndian>()?,
+                minimum: buf.read_u32::<BigEndian>()?,
+            },
+            _ => {
+                let mut data = vec![0u8; length as usize];
+                buf.read_exact(&mut data)?;
+                Self::Raw(data)
+            }
+        };
+
+        buf.set_position(end);
+        Ok(rdata)
+    }
+}
+
+/// Builds the response to `query`, answering from `zone` when the question's
+/// name is authoritative for us and otherwise resolving it through
+/// `resolver`; mirrors back an EDNS0 OPT record if the client sent one.
+fn build_response(query: &DnsMessage, resolver: &Mutex<Resolver>, zone: &Option<Zone>) -> DnsMessage {
+    let mut response = query.clone();
+    response.header.qr = true;
+    response.header.ra = true;
+
+    response.answers = query
+        .questions
+        .iter()
+        .filter_map(|question| {
+            if let Some(zone) = zone {
+                if let Some(answers) = zone.lookup(&question.name, question.r#type) {
+                    response.header.aa = true;
+                    return Some(answers.to_vec());
+                }
+                if zone.contains_name(&question.name) {
+                    // The name is ours but has no record of this type: NOERROR
+                    // with no answers (NODATA), not NXDOMAIN — the name
+                    // itself exists.
+                    response.header.aa = true;
+                    return None;
+                }
+            }
+
+            match resolver.lock().unwrap().resolve(question) {
+                Ok(answers) => Some(answers),
+                Err(e) => {
+                    eprintln!("Failed to resolve {}: {e}", question.name);
+                    response.header.rcode = ResponseCode::ServerFailure;
+                    None
+                }
+            }
+        })
+        .flatten()
+        .collect();
+    response.header.ancount = response.answers.len() as u16;
+
+    response.edns = query.edns.map(|_| EdnsOpt {
+        udp_payload_size: OUR_UDP_PAYLOAD_SIZE,
+        extended_rcode: 0,
+        version: 0,
+        flags: 0,
+    });
+    response.header.arcount = response.edns.is_some() as u16;
+
+    response
+}
+
+fn run_udp_server(socket: UdpSocket, resolver: Arc<Mutex<Resolver>>, zone: Arc<Option<Zone>>) {
+    let mut buf = [0u8; OUR_UDP_PAYLOAD_SIZE as usize];
 
     loop {
-        match udp_socket.recv_from(&mut buf) {
+        match socket.recv_from(&mut buf) {
             Ok((size, source)) => {
                 let mut cursor = Cursor::new(&buf[0..size]);
 
-                let header = DnsHeader::default();
-                let questions = DnsQuestion::default();
-                let answers = DnsAnswer::default();
+                let mut query = DnsMessage::default();
+                if let Err(e) = query.read(&mut cursor) {
+                    eprintln!("Failed to parse query: {e}");
+                    continue;
+                }
 
-                let mut response = DnsMessage::new(header, vec![questions], vec![answers]);
-                response.read(&mut cursor).unwrap();
+                let response = build_response(&query, &resolver, &zone);
+                let max_size = query
+                    .edns
+                    .map(|opt| opt.udp_payload_size as usize)
+                    .unwrap_or(STANDARD_UDP_SIZE);
 
-                udp_socket
-                    .send_to(&response.to_be_bytes(), source)
+                socket
+                    .send_to(&response.to_be_bytes(max_size), source)
                     .expect("Failed to send response");
             }
             Err(e) => {
@@ -363,3 +767,168 @@ fn main() {
         }
     }
 }
+
+/// Handles one TCP-retried query: a 2-byte big-endian length prefix
+/// followed by the message itself (RFC 1035 section 4.2.2). TCP responses
+/// aren't subject to the UDP size limit, so they're never truncated.
+fn handle_tcp_connection(
+    mut stream: TcpStream,
+    resolver: &Mutex<Resolver>,
+    zone: &Option<Zone>,
+) -> Result<()> {
+    let size = stream.read_u16::<BigEndian>()?;
+    let mut data = vec![0u8; size as usize];
+    stream.read_exact(&mut data)?;
+
+    let mut query = DnsMessage::default();
+    query.read(&mut Cursor::new(data.as_slice()))?;
+
+    let response = build_response(&query, resolver, zone).to_be_bytes(u16::MAX as usize);
+    stream.write_u16::<BigEndian>(response.len() as u16)?;
+    stream.write_all(&response)?;
+
+    Ok(())
+}
+
+fn run_tcp_server(listener: TcpListener, resolver: Arc<Mutex<Resolver>>, zone: Arc<Option<Zone>>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let resolver = Arc::clone(&resolver);
+                let zone = Arc::clone(&zone);
+                thread::spawn(move || {
+                    if let Err(e) = handle_tcp_connection(stream, &resolver, &zone) {
+                        eprintln!("Failed to handle TCP query: {e}");
+                    }
+                });
+            }
+            Err(e) => eprintln!("Error accepting TCP connection: {e}"),
+        }
+    }
+}
+
+/// Looks for a `--zone <path>` flag among the process arguments, loading and
+/// returning the parsed zone if present; with no flag the server runs as a
+/// plain caching resolver.
+fn load_zone_from_args() -> Option<Zone> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == "--zone" {
+            let path = args.next().expect("--zone requires a path argument");
+            return Some(Zone::load(&path).expect("Failed to load zone file"));
+        }
+    }
+    None
+}
+
+fn main() {
+    let udp_socket = UdpSocket::bind("127.0.0.1:2053").expect("Failed to bind to UDP address");
+    let tcp_listener =
+        TcpListener::bind("127.0.0.1:2053").expect("Failed to bind to TCP address");
+    let resolver = Arc::new(Mutex::new(
+        Resolver::new(DEFAULT_UPSTREAM).expect("Failed to create resolver"),
+    ));
+    let zone = Arc::new(load_zone_from_args());
+
+    let tcp_resolver = Arc::clone(&resolver);
+    let tcp_zone = Arc::clone(&zone);
+    thread::spawn(move || run_tcp_server(tcp_listener, tcp_resolver, tcp_zone));
+
+    run_udp_server(udp_socket, resolver, zone);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_name_round_trips_an_uncompressed_name() {
+        let bytes = encode_name("www.example.com").unwrap();
+        let name = read_name(&mut Cursor::new(bytes.as_slice())).unwrap();
+        assert_eq!(name, "www.example.com");
+    }
+
+    #[test]
+    fn read_name_follows_a_compression_pointer() {
+        // "example.com" at offset 0, then "www" pointing back at it.
+        let mut bytes = encode_name("example.com").unwrap();
+        let pointer_offset = bytes.len() as u64;
+        bytes.push(3);
+        bytes.extend(b"www");
+        bytes.extend(0xC000u16.to_be_bytes()); // pointer to offset 0
+
+        let mut cursor = Cursor::new(bytes.as_slice());
+        cursor.set_position(pointer_offset);
+        let name = read_name(&mut cursor).unwrap();
+        assert_eq!(name, "www.example.com");
+    }
+
+    #[test]
+    fn rdata_round_trips_mx() {
+        let rdata = RData::MX {
+            preference: 10,
+            exchange: "mail.example.com".to_string(),
+        };
+        let bytes = rdata.to_be_bytes().unwrap();
+        let decoded =
+            RData::read(&mut Cursor::new(bytes.as_slice()), RecordType::MX, bytes.len() as u16)
+                .unwrap();
+        assert!(matches!(
+            decoded,
+            RData::MX { preference: 10, exchange } if exchange == "mail.example.com"
+        ));
+    }
+
+    #[test]
+    fn rdata_round_trips_a_and_aaaa() {
+        let a = RData::A(Ipv4Addr::new(203, 0, 113, 10));
+        let bytes = a.to_be_bytes().unwrap();
+        let decoded =
+            RData::read(&mut Cursor::new(bytes.as_slice()), RecordType::A, bytes.len() as u16)
+                .unwrap();
+        assert!(matches!(decoded, RData::A(addr) if addr == Ipv4Addr::new(203, 0, 113, 10)));
+
+        let aaaa = RData::AAAA(Ipv6Addr::LOCALHOST);
+        let bytes = aaaa.to_be_bytes().unwrap();
+        let decoded = RData::read(
+            &mut Cursor::new(bytes.as_slice()),
+            RecordType::AAAA,
+            bytes.len() as u16,
+        )
+        .unwrap();
+        assert!(matches!(decoded, RData::AAAA(addr) if addr == Ipv6Addr::LOCALHOST));
+    }
+
+    #[test]
+    fn rdata_falls_back_to_raw_for_an_unknown_type() {
+        let httpsrr = RecordType::Unknown(65);
+        let bytes = vec![0x00, 0x01, 0x02];
+        let decoded =
+            RData::read(&mut Cursor::new(bytes.as_slice()), httpsrr, bytes.len() as u16).unwrap();
+        assert!(matches!(decoded, RData::Raw(data) if data == bytes));
+    }
+
+    #[test]
+    fn read_name_rejects_a_pointer_loop() {
+        // Two labels that point at each other forever.
+        let mut bytes = Vec::new();
+        bytes.extend(0xC002u16.to_be_bytes()); // offset 0: pointer to offset 2
+        bytes.extend(0xC000u16.to_be_bytes()); // offset 2: pointer to offset 0
+
+        let result = read_name(&mut Cursor::new(bytes.as_slice()));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn encode_name_converts_unicode_labels_to_punycode() {
+        let bytes = encode_name("münchen.de").unwrap();
+        assert_eq!(bytes, b"\x0exn--mnchen-3ya\x02de\x00");
+    }
+
+    #[test]
+    fn read_name_round_trips_an_internationalized_name() {
+        let bytes = encode_name("münchen.de").unwrap();
+        let name = read_name(&mut Cursor::new(bytes.as_slice())).unwrap();
+        assert_eq!(name, "münchen.de");
+    }
+}
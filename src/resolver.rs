@@ -0,0 +1,133 @@
+use crate::{DnsAnswer, DnsHeader, DnsMessage, DnsQuestion, RecordClass, RecordType, ResponseCode};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+type CacheKey = (String, RecordType, RecordClass);
+
+/// How long we wait for an upstream reply before giving up. `resolve` runs
+/// on the UDP server's single thread while holding the shared resolver
+/// mutex, so an upstream datagram lost to routine packet loss must not be
+/// allowed to hang the socket forever.
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How many stray/mismatched datagrams we'll read on our own socket while
+/// waiting for the reply to a given query, before giving up. Guards against
+/// a flood of unrelated or spoofed packets pinning us inside `forward`.
+const MAX_MISMATCHED_RESPONSES: usize = 5;
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    answers: Vec<DnsAnswer>,
+    expires_at: Instant,
+}
+
+/// A caching forwarder: questions that miss the cache are sent to `upstream`
+/// over our own socket and the decoded answers are cached under their TTL.
+pub(crate) struct Resolver {
+    upstream: SocketAddr,
+    socket: UdpSocket,
+    cache: HashMap<CacheKey, CacheEntry>,
+    next_id: u16,
+}
+
+impl Resolver {
+    pub(crate) fn new(upstream: impl ToSocketAddrs) -> Result<Self> {
+        let upstream = upstream
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("Upstream has no resolvable address"))?;
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+
+        Ok(Self {
+            upstream,
+            socket,
+            cache: HashMap::new(),
+            next_id: 1,
+        })
+    }
+
+    pub(crate) fn resolve(&mut self, question: &DnsQuestion) -> Result<Vec<DnsAnswer>> {
+        let key = (question.name.clone(), question.r#type, question.class);
+
+        if let Some(entry) = self.cache.get(&key) {
+            let now = Instant::now();
+            if entry.expires_at > now {
+                let remaining = (entry.expires_at - now).as_secs() as u32;
+                let mut answers = entry.answers.clone();
+                answers.iter_mut().for_each(|a| a.ttl = remaining);
+                return Ok(answers);
+            }
+            self.cache.remove(&key);
+        }
+
+        let answers = self.forward(question)?;
+        let min_ttl = answers.iter().map(|a| a.ttl).min().unwrap_or(0);
+        let expires_at = Instant::now() + Duration::from_secs(min_ttl as u64);
+        self.cache.insert(
+            key,
+            CacheEntry {
+                answers: answers.clone(),
+                expires_at,
+            },
+        );
+
+        Ok(answers)
+    }
+
+    /// Sends `question` upstream under a fresh query ID and waits for a
+    /// reply that echoes both that ID and the question, discarding anything
+    /// else (stale retransmits, responses to earlier queries on this same
+    /// socket, or spoofed datagrams) until it sees one or runs out of
+    /// patience.
+    fn forward(&mut self, question: &DnsQuestion) -> Result<Vec<DnsAnswer>> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let header = DnsHeader::new(
+            id,
+            false,
+            0,
+            false,
+            false,
+            true,
+            false,
+            false,
+            ResponseCode::NoError,
+            1,
+            0,
+            0,
+            0,
+        );
+        let query = DnsMessage::new(header, vec![question.clone()], vec![]);
+        self.socket
+            .send_to(&query.to_be_bytes(crate::STANDARD_UDP_SIZE), self.upstream)?;
+
+        let mut buf = [0u8; crate::STANDARD_UDP_SIZE];
+        for _ in 0..MAX_MISMATCHED_RESPONSES {
+            let size = self.socket.recv(&mut buf)?;
+            let mut cursor = Cursor::new(&buf[0..size]);
+
+            let mut response = DnsMessage::default();
+            if response.read(&mut cursor).is_err() {
+                continue;
+            }
+
+            let answers_question = response.questions.first().is_some_and(|q| {
+                q.name == question.name && q.r#type == question.r#type && q.class == question.class
+            });
+
+            if response.header.id == id && answers_question {
+                return Ok(response.answers);
+            }
+        }
+
+        Err(anyhow!(
+            "No matching response from upstream for {}",
+            question.name
+        ))
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{DnsAnswer, RData, RecordClass, RecordType};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::str::FromStr;
+
+/// An authoritative zone loaded from a master-file (RFC 1035 section 5)
+/// style zone file, one `name TTL CLASS TYPE RDATA` record per line.
+pub(crate) struct Zone {
+    records: HashMap<(String, RecordType), Vec<DnsAnswer>>,
+}
+
+impl Zone {
+    pub(crate) fn load(path: impl AsRef<Path>) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Answers for `name`/`r#type`, if the zone has any.
+    pub(crate) fn lookup(&self, name: &str, r#type: RecordType) -> Option<&[DnsAnswer]> {
+        self.records
+            .get(&(name.to_string(), r#type))
+            .map(Vec::as_slice)
+    }
+
+    /// Whether `name` exists in the zone under any record type.
+    pub(crate) fn contains_name(&self, name: &str) -> bool {
+        self.records.keys().any(|(n, _)| n == name)
+    }
+}
+
+impl FromStr for Zone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut records: HashMap<(String, RecordType), Vec<DnsAnswer>> = HashMap::new();
+        let mut origin = String::new();
+
+        for line in s.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("$ORIGIN") {
+                origin = rest.trim().trim_end_matches('.').to_string();
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let name = fields
+                .next()
+                .ok_or_else(|| anyhow!("Missing name in zone line: {line}"))?;
+            let ttl: u32 = fields
+                .next()
+                .ok_or_else(|| anyhow!("Missing TTL in zone line: {line}"))?
+                .parse()?;
+            let class = fields
+                .next()
+                .ok_or_else(|| anyhow!("Missing class in zone line: {line}"))?;
+            if !class.eq_ignore_ascii_case("IN") {
+                return Err(anyhow!("Unsupported record class: {class}"));
+            }
+            let r#type = fields
+                .next()
+                .ok_or_else(|| anyhow!("Missing type in zone line: {line}"))?;
+            let rdata_tokens: Vec<&str> = fields.collect();
+
+            let name = resolve_name(name, &origin);
+            let r#type = parse_type(r#type)?;
+            let rdata = parse_rdata(r#type, &rdata_tokens, &origin)?;
+
+            let answer = DnsAnswer::new(name.clone(), r#type, RecordClass::IN, ttl, rdata);
+            records.entry((name, r#type)).or_default().push(answer);
+        }
+
+        Ok(Self { records })
+    }
+}
+
+/// Resolves a zone-file name token to an absolute name: `@` means the
+/// current origin, a trailing dot means the name is already absolute, and
+/// anything else is relative to the origin.
+fn resolve_name(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if let Some(absolute) = name.strip_suffix('.') {
+        absolute.to_string()
+    } else if origin.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}.{origin}")
+    }
+}
+
+fn parse_type(s: &str) -> Result<RecordType> {
+    Ok(match s.to_ascii_uppercase().as_str() {
+        "A" => RecordType::A,
+        "NS" => RecordType::NS,
+        "CNAME" => RecordType::CNAME,
+        "SOA" => RecordType::SOA,
+        "MX" => RecordType::MX,
+        "TXT" => RecordType::TXT,
+        "AAAA" => RecordType::AAAA,
+        _ => return Err(anyhow!("Unsupported record type: {s}")),
+    })
+}
+
+fn parse_rdata(r#type: RecordType, tokens: &[&str], origin: &str) -> Result<RData> {
+    Ok(match r#type {
+        RecordType::A => RData::A(
+            tokens
+                .first()
+                .ok_or_else(|| anyhow!("Missing A address"))?
+                .parse::<Ipv4Addr>()?,
+        ),
+        RecordType::AAAA => RData::AAAA(
+            tokens
+                .first()
+                .ok_or_else(|| anyhow!("Missing AAAA address"))?
+                .parse::<Ipv6Addr>()?,
+        ),
+        RecordType::CNAME => RData::CNAME(resolve_name(
+            tokens.first().ok_or_else(|| anyhow!("Missing CNAME target"))?,
+            origin,
+        )),
+        RecordType::NS => RData::NS(resolve_name(
+            tokens.first().ok_or_else(|| anyhow!("Missing NS target"))?,
+            origin,
+        )),
+        RecordType::MX => {
+            let preference = tokens
+                .first()
+                .ok_or_else(|| anyhow!("Missing MX preference"))?
+                .parse()?;
+            let exchange = resolve_name(
+                tokens.get(1).ok_or_else(|| anyhow!("Missing MX exchange"))?,
+                origin,
+            );
+            RData::MX {
+                preference,
+                exchange,
+            }
+        }
+        RecordType::TXT => RData::TXT(vec![tokens.join(" ").trim_matches('"').to_string()]),
+        RecordType::SOA => RData::SOA {
+            mname: resolve_name(
+                tokens.first().ok_or_else(|| anyhow!("Missing SOA mname"))?,
+                origin,
+            ),
+            rname: resolve_name(
+                tokens.get(1).ok_or_else(|| anyhow!("Missing SOA rname"))?,
+                origin,
+            ),
+            serial: tokens
+                .get(2)
+                .ok_or_else(|| anyhow!("Missing SOA serial"))?
+                .parse()?,
+            refresh: tokens
+                .get(3)
+                .ok_or_else(|| anyhow!("Missing SOA refresh"))?
+                .parse()?,
+            retry: tokens
+                .get(4)
+                .ok_or_else(|| anyhow!("Missing SOA retry"))?
+                .parse()?,
+            expire: tokens
+                .get(5)
+                .ok_or_else(|| anyhow!("Missing SOA expire"))?
+                .parse()?,
+            minimum: tokens
+                .get(6)
+                .ok_or_else(|| anyhow!("Missing SOA minimum"))?
+                .parse()?,
+        },
+        _ => return Err(anyhow!("Unsupported RDATA for record type {:?}", r#type)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_name_handles_origin_at_and_absolute() {
+        assert_eq!(resolve_name("@", "example.com"), "example.com");
+        assert_eq!(resolve_name("www.example.com.", "example.com"), "www.example.com");
+        assert_eq!(resolve_name("www", "example.com"), "www.example.com");
+        assert_eq!(resolve_name("www", ""), "www");
+    }
+
+    #[test]
+    fn from_str_parses_records_and_resolves_relative_names() {
+        let zone: Zone = "
+            $ORIGIN example.com
+            @       3600 IN SOA  ns1 admin 1 7200 3600 1209600 3600
+            @       3600 IN NS   ns1
+            www     3600 IN A    203.0.113.10
+            ns1     3600 IN A    203.0.113.1
+        "
+        .parse()
+        .unwrap();
+
+        let a = zone.lookup("www.example.com", RecordType::A).unwrap();
+        assert_eq!(a.len(), 1);
+        assert!(matches!(a[0].rdata, RData::A(addr) if addr == Ipv4Addr::new(203, 0, 113, 10)));
+
+        assert!(zone.lookup("example.com", RecordType::SOA).is_some());
+        assert!(zone.contains_name("ns1.example.com"));
+        assert!(zone.lookup("missing.example.com", RecordType::A).is_none());
+    }
+
+    #[test]
+    fn from_str_rejects_unsupported_class() {
+        let result: Result<Zone> = "example.com 3600 CH TXT hello".parse();
+        assert!(result.is_err());
+    }
+}